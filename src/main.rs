@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
 use std::io;
@@ -5,26 +6,139 @@ use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use notify::{RecommendedWatcher, RecursiveMode, Watcher};
-use serde::{Deserialize, Serialize};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{Config as NotifyConfig, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use tokio::time;
 use tracing::{error, info, warn};
 
 use tokio::sync::mpsc;
 
+/// How the watcher should be notified about filesystem changes.
+///
+/// `Native` uses the OS's native notification mechanism (inotify, kqueue, ...) and is the
+/// default. `Poll` falls back to periodic stat-based polling, which is slower but works on
+/// network filesystems, some containers, and FUSE mounts where native events are never
+/// delivered.
+#[derive(Debug, Clone, Default)]
+enum WatcherKind {
+    #[default]
+    Native,
+    Poll(Duration),
+}
+
+/// Parses simple durations like `"5s"`, `"250ms"`, `"2m"`, or `"1h"`.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split_at = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(|| {
+        format!("duration '{}' is missing a unit (expected ms, s, m, or h)", s)
+    })?;
+    let (value, unit) = s.split_at(split_at);
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("invalid duration value in '{}'", s))?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        "m" => Ok(Duration::from_secs(value * 60)),
+        "h" => Ok(Duration::from_secs(value * 3600)),
+        other => Err(format!("unknown duration unit '{}' in '{}'", other, s)),
+    }
+}
+
+impl<'de> Deserialize<'de> for WatcherKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Named(String),
+            Poll { poll: String },
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Named(name) if name == "native" => Ok(WatcherKind::Native),
+            Repr::Named(name) => Err(D::Error::custom(format!("unknown watcher kind '{}'", name))),
+            Repr::Poll { poll } => {
+                let delay = parse_duration(&poll).map_err(D::Error::custom)?;
+                Ok(WatcherKind::Poll(delay))
+            }
+        }
+    }
+}
+
+impl Serialize for WatcherKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            WatcherKind::Native => serializer.serialize_str("native"),
+            WatcherKind::Poll(delay) => {
+                #[derive(Serialize)]
+                struct PollRepr {
+                    poll: String,
+                }
+                // Keep the unit sub-second so a config with e.g. `"250ms"` round-trips instead
+                // of silently truncating to `"0s"`.
+                let poll = if delay.as_secs() > 0 && delay.subsec_millis() == 0 {
+                    format!("{}s", delay.as_secs())
+                } else {
+                    format!("{}ms", delay.as_millis())
+                };
+                PollRepr { poll }.serialize(serializer)
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Config {
     watch_dirs: Vec<String>,
+    /// Gitignore-style glob patterns (`*`, `**`, `!`-negation) matched against paths relative
+    /// to the watch dir they apply to (e.g. `"ignoreme/"`, not `"./testdir/ignoreme"`). A
+    /// `.gitignore` at the root of each watch dir is also honored automatically. Pre-existing
+    /// entries written as a full path under the watch dir are detected and rewritten to be
+    /// root-relative, so old configs keep working.
     ignore_dirs: Vec<String>,
+    /// Fallback mode applied when `file_permission`/`dir_permission` are unset.
     desired_permission: String,
+    /// Mode applied to regular files. Falls back to `desired_permission` when unset.
+    #[serde(default)]
+    file_permission: Option<String>,
+    /// Mode applied to directories. Falls back to `desired_permission` when unset.
+    #[serde(default)]
+    dir_permission: Option<String>,
+    #[serde(default)]
+    watcher: WatcherKind,
+    /// How long to wait for the event stream to go quiet before running a check, coalescing
+    /// bursts of events (e.g. an editor's write + rename + chmod on save) into one pass.
+    #[serde(default = "default_debounce")]
+    debounce: String,
+    /// When set (or when run with `--dry-run`), violations are logged but never fixed.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+fn default_debounce() -> String {
+    "100ms".to_string()
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             watch_dirs: vec!["./testdir".to_string()],
-            ignore_dirs: vec!["./testdir/ignoreme".to_string()],
+            ignore_dirs: vec!["ignoreme/".to_string()],
             desired_permission: "777".to_string(),
+            file_permission: None,
+            dir_permission: None,
+            watcher: WatcherKind::default(),
+            debounce: default_debounce(),
+            dry_run: false,
         }
     }
 }
@@ -51,71 +165,271 @@ impl Config {
     }
 }
 
+/// Strips a leading `root/` (or `./root/`) from `pattern` so that old-style entries written as
+/// a full path under the watch dir (e.g. `"./testdir/ignoreme"`) still anchor correctly once
+/// `root` becomes the `Gitignore` base, instead of silently matching nothing.
+fn normalize_ignore_pattern(root: &str, pattern: &str) -> String {
+    let root = root.trim_start_matches("./").trim_end_matches('/');
+    let pattern = pattern.trim_start_matches("./");
+
+    match pattern.strip_prefix(root) {
+        Some("") => ".".to_string(),
+        Some(rest) if rest.starts_with('/') => format!("/{}", rest.trim_start_matches('/')),
+        _ => pattern.to_string(),
+    }
+}
+
+/// Builds a gitignore-style matcher for `root` from the configured `patterns`, additionally
+/// honoring a `.gitignore` file at the root of the directory if one exists.
+fn build_ignore_matcher(root: &str, patterns: &[String]) -> io::Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(root);
+
+    for pattern in patterns {
+        let pattern = normalize_ignore_pattern(root, pattern);
+        builder
+            .add_line(None, &pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    }
+
+    let gitignore_path = Path::new(root).join(".gitignore");
+    if let Ok(content) = fs::read_to_string(&gitignore_path) {
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            builder
+                .add_line(None, line)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        }
+    }
+
+    builder
+        .build()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Canonicalizes `path`, falling back to canonicalizing its parent directory (for paths from
+/// delete events, which may no longer exist by the time we look at them) and finally to the
+/// path as-is if even that fails.
+fn canonicalize_lossy(path: &Path) -> PathBuf {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return canonical;
+    }
+    if let Some(parent) = path.parent() {
+        if let Ok(canonical_parent) = fs::canonicalize(parent) {
+            return match path.file_name() {
+                Some(name) => canonical_parent.join(name),
+                None => canonical_parent,
+            };
+        }
+    }
+    path.to_path_buf()
+}
+
 struct PermissionChecker {
     config: Config,
-    watcher: RecommendedWatcher,
+    watcher: Box<dyn Watcher + Send>,
+    ignore_matchers: HashMap<String, Gitignore>,
+    /// Canonicalized form of each `config.watch_dirs` entry, keyed by the original config
+    /// string. `notify` reports absolute paths regardless of how `watch_dirs` was configured,
+    /// so matching a relative `watch_dirs` entry against a `notify::Event` path requires
+    /// comparing canonical forms rather than raw strings.
+    canonical_watch_dirs: HashMap<String, PathBuf>,
 }
 
 impl PermissionChecker {
-    async fn new(config: Config, event_tx: mpsc::Sender<()>) -> io::Result<Self> {
-        let watcher = notify::recommended_watcher(move |res| match res {
-            Ok(_) => {
-                info!("File system event detected");
-                if let Err(e) = event_tx.blocking_send(()) {
+    async fn new(config: Config, event_tx: mpsc::Sender<notify::Event>) -> io::Result<Self> {
+        let handler = move |res: notify::Result<notify::Event>| match res {
+            Ok(event) => {
+                info!("File system event detected: {:?}", event.kind);
+                if let Err(e) = event_tx.blocking_send(event) {
                     error!("Failed to send event notification: {}", e);
                 }
             }
             Err(e) => error!("Watch error: {:?}", e),
+        };
+
+        let watcher: Box<dyn Watcher + Send> = match config.watcher {
+            WatcherKind::Native => {
+                let watcher: RecommendedWatcher =
+                    notify::recommended_watcher(handler).map_err(io::Error::other)?;
+                Box::new(watcher)
+            }
+            WatcherKind::Poll(delay) => {
+                info!("Using poll watcher with a {:?} interval", delay);
+                let watcher =
+                    PollWatcher::new(handler, NotifyConfig::default().with_poll_interval(delay))
+                        .map_err(io::Error::other)?;
+                Box::new(watcher)
+            }
+        };
+
+        let mut ignore_matchers = HashMap::new();
+        let mut canonical_watch_dirs = HashMap::new();
+        for dir in &config.watch_dirs {
+            let matcher = build_ignore_matcher(dir, &config.ignore_dirs)?;
+            ignore_matchers.insert(dir.clone(), matcher);
+            canonical_watch_dirs.insert(dir.clone(), fs::canonicalize(dir)?);
+        }
+
+        Ok(Self {
+            config,
+            watcher,
+            ignore_matchers,
+            canonical_watch_dirs,
         })
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    }
 
-        Ok(Self { config, watcher })
+    fn is_ignored(&self, dir: &str, path: &Path, is_dir: bool) -> bool {
+        match self.ignore_matchers.get(dir) {
+            Some(matcher) if matcher.matched(path, is_dir).is_ignore() => {
+                info!("Ignoring {} because it matches an ignore pattern", path.display());
+                true
+            }
+            _ => false,
+        }
     }
 
-    fn should_process_file(&self, path: &Path) -> bool {
-        // Check if file is in ignored directories
-        for dir in &self.config.ignore_dirs {
-            if path.starts_with(dir) {
-                // log ignored file and the directory that caused it
+    /// Like `is_ignored`, but also checks whether any ancestor directory (up to `dir`) is
+    /// ignored. Needed for single-path lookups (e.g. from a notify event) where, unlike a
+    /// `WalkDir` traversal, there's no `filter_entry` already pruning ignored subtrees.
+    fn is_ignored_recursive(&self, dir: &str, path: &Path, is_dir: bool) -> bool {
+        match self.ignore_matchers.get(dir) {
+            Some(matcher) if matcher.matched_path_or_any_parents(path, is_dir).is_ignore() => {
                 info!(
-                    "Ignoring file {} because it is in the ignored directory {}",
-                    path.display(),
-                    dir
+                    "Ignoring {} because it or a parent directory matches an ignore pattern",
+                    path.display()
                 );
-                return false;
+                true
             }
+            _ => false,
         }
-        true
+    }
+
+    /// Returns the desired mode for an entry, using `file_permission`/`dir_permission` when
+    /// set and falling back to `desired_permission` otherwise.
+    fn desired_mode_for(&self, is_dir: bool) -> io::Result<u32> {
+        let mode_str = if is_dir {
+            self.config
+                .dir_permission
+                .as_deref()
+                .unwrap_or(&self.config.desired_permission)
+        } else {
+            self.config
+                .file_permission
+                .as_deref()
+                .unwrap_or(&self.config.desired_permission)
+        };
+
+        u32::from_str_radix(mode_str, 8).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
     }
 
     async fn check_permissions(&self, dir: &str) -> io::Result<Vec<PathBuf>> {
         let mut files_with_wrong_permission = Vec::new();
-        let desired_mode = u32::from_str_radix(&self.config.desired_permission, 8)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
 
         for entry in walkdir::WalkDir::new(dir)
             .follow_links(true)
             .into_iter()
+            .filter_entry(|e| !self.is_ignored(dir, e.path(), e.file_type().is_dir()))
             .filter_map(|e| e.ok())
         {
             let path = entry.path();
-            if self.should_process_file(path) {
-                let metadata = fs::metadata(path)?;
+            let metadata = fs::metadata(path)?;
+            let desired_mode = self.desired_mode_for(metadata.is_dir())?;
+            if metadata.permissions().mode() & 0o777 != desired_mode {
+                files_with_wrong_permission.push(path.to_path_buf());
+            }
+        }
+
+        Ok(files_with_wrong_permission)
+    }
+
+    /// Returns the configured watch dir that contains `path`, if any. `path` is canonicalized
+    /// before comparison since `notify` reports absolute paths even when `watch_dirs` is
+    /// configured with a relative entry like `"./testdir"`.
+    fn watch_dir_for(&self, path: &Path) -> Option<&str> {
+        let canonical_path = canonicalize_lossy(path);
+        self.canonical_watch_dirs
+            .iter()
+            .find(|(_, root)| canonical_path.starts_with(root.as_path()))
+            .map(|(dir, _)| dir.as_str())
+    }
+
+    /// Checks and fixes permissions for only the given paths, rather than re-walking every
+    /// watched directory. Directories are walked through their whole subtree so that a
+    /// directory-create event also picks up files created alongside it.
+    async fn check_paths(&self, paths: &[PathBuf]) -> io::Result<()> {
+        let mut files_with_wrong_permission = Vec::new();
+
+        for path in paths {
+            let Some(dir) = self.watch_dir_for(path) else {
+                continue;
+            };
+            // The ignore matchers are rooted at `dir` as configured, so re-express the event
+            // path under `dir` before matching it.
+            let under_dir = match canonicalize_lossy(path).strip_prefix(&self.canonical_watch_dirs[dir]) {
+                Ok(rel) => Path::new(dir).join(rel),
+                Err(_) => path.clone(),
+            };
+            let path = &under_dir;
+
+            let metadata = match fs::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            if self.is_ignored_recursive(dir, path, metadata.is_dir()) {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                for entry in walkdir::WalkDir::new(path)
+                    .follow_links(true)
+                    .into_iter()
+                    .filter_entry(|e| !self.is_ignored(dir, e.path(), e.file_type().is_dir()))
+                    .filter_map(|e| e.ok())
+                {
+                    let entry_path = entry.path();
+                    let entry_metadata = fs::metadata(entry_path)?;
+                    let desired_mode = self.desired_mode_for(entry_metadata.is_dir())?;
+                    if entry_metadata.permissions().mode() & 0o777 != desired_mode {
+                        files_with_wrong_permission.push(entry_path.to_path_buf());
+                    }
+                }
+            } else {
+                let desired_mode = self.desired_mode_for(false)?;
                 if metadata.permissions().mode() & 0o777 != desired_mode {
-                    files_with_wrong_permission.push(path.to_path_buf());
+                    files_with_wrong_permission.push(path.clone());
                 }
             }
         }
 
-        Ok(files_with_wrong_permission)
+        if !files_with_wrong_permission.is_empty() {
+            self.change_permissions(files_with_wrong_permission).await?;
+        }
+
+        Ok(())
     }
 
     async fn change_permissions(&self, files: Vec<PathBuf>) -> io::Result<()> {
-        let desired_mode = u32::from_str_radix(&self.config.desired_permission, 8)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
-
         for file in files {
-            let mut perms = fs::metadata(&file)?.permissions();
+            let metadata = fs::metadata(&file)?;
+            let desired_mode = self.desired_mode_for(metadata.is_dir())?;
+            let current_mode = metadata.permissions().mode() & 0o777;
+
+            if self.config.dry_run {
+                info!(
+                    "[dry run] {} is {:o}, would change to {:o}",
+                    file.display(),
+                    current_mode,
+                    desired_mode
+                );
+                continue;
+            }
+
+            let mut perms = metadata.permissions();
             perms.set_mode(desired_mode);
             fs::set_permissions(&file, perms)?;
             info!(
@@ -137,10 +451,15 @@ impl PermissionChecker {
     }
 
     async fn run_check(&self) -> io::Result<()> {
+        let mut total_violations = 0;
+
         for dir in &self.config.watch_dirs {
             match self.check_permissions(dir).await {
                 Ok(files) => {
-                    if !files.is_empty() {
+                    let count = files.len();
+                    total_violations += count;
+                    if count > 0 {
+                        info!("{}: {} violation(s)", dir, count);
                         self.change_permissions(files).await?;
                     }
                 }
@@ -149,6 +468,14 @@ impl PermissionChecker {
                 }
             }
         }
+
+        info!(
+            "Check complete: {} violation(s) across {} watched dir(s){}",
+            total_violations,
+            self.config.watch_dirs.len(),
+            if self.config.dry_run { " (dry run)" } else { "" }
+        );
+
         Ok(())
     }
 }
@@ -159,7 +486,15 @@ async fn main() -> io::Result<()> {
     tracing_subscriber::fmt::init();
 
     // Load configuration
-    let config = Config::load().await?;
+    let mut config = Config::load().await?;
+
+    // `--dry-run` on the command line overrides the config file
+    if env::args().any(|arg| arg == "--dry-run") {
+        config.dry_run = true;
+    }
+    if config.dry_run {
+        info!("Running in dry-run mode: violations will be reported but not fixed");
+    }
 
     // check if dirs are available
     for dir in &config.watch_dirs {
@@ -171,8 +506,11 @@ async fn main() -> io::Result<()> {
         }
     }
 
+    let debounce = parse_duration(&config.debounce)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
     // Create a channel for watcher events
-    let (event_tx, mut event_rx) = mpsc::channel(100);
+    let (event_tx, mut event_rx) = mpsc::channel::<notify::Event>(100);
 
     let mut checker = PermissionChecker::new(config, event_tx).await?;
 
@@ -199,14 +537,82 @@ async fn main() -> io::Result<()> {
                     error!("Error during periodic check: {}", e);
                 }
             }
-            Some(_) = event_rx.recv() => {
-                info!("Running check due to file system event");
-                // Add a small delay to allow for multiple simultaneous events
-                time::sleep(Duration::from_millis(100)).await;
-                if let Err(e) = checker.run_check().await {
+            Some(event) = event_rx.recv() => {
+                // Debounce: accumulate paths from this burst of events until the channel
+                // goes quiet for a full `debounce` interval, then run one check.
+                let mut pending_paths: HashSet<PathBuf> = event.paths.into_iter().collect();
+                loop {
+                    tokio::select! {
+                        _ = time::sleep(debounce) => break,
+                        Some(event) = event_rx.recv() => {
+                            pending_paths.extend(event.paths);
+                        }
+                    }
+                }
+
+                info!("Running check due to {} changed path(s)", pending_paths.len());
+                let paths: Vec<PathBuf> = pending_paths.into_iter().collect();
+                if let Err(e) = checker.check_paths(&paths).await {
                     error!("Error during event-triggered check: {}", e);
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a fresh, empty temp directory for a test and returns its path.
+    fn temp_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!(
+            "dir-permission-watcher-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn check_paths_fixes_mode_for_absolute_event_path_under_relative_watch_dir() {
+        let base = temp_dir("check-paths-relative");
+        fs::create_dir_all(base.join("watchme")).unwrap();
+        let file_path = base.join("watchme").join("foo.txt");
+        fs::write(&file_path, b"hello").unwrap();
+        fs::set_permissions(&file_path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let original_cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&base).unwrap();
+
+        let config = Config {
+            watch_dirs: vec!["./watchme".to_string()],
+            ignore_dirs: vec![],
+            desired_permission: "644".to_string(),
+            file_permission: None,
+            dir_permission: None,
+            watcher: WatcherKind::Native,
+            debounce: default_debounce(),
+            dry_run: false,
+        };
+
+        let (event_tx, _event_rx) = mpsc::channel(1);
+        let checker = PermissionChecker::new(config, event_tx).await.unwrap();
+
+        // notify reports absolute, canonicalized paths even though watch_dirs is configured
+        // relative to the process cwd - exercise that mismatch directly.
+        let event_path = fs::canonicalize(&file_path).unwrap();
+        let result = checker.check_paths(&[event_path]).await;
+
+        std::env::set_current_dir(original_cwd).unwrap();
+        result.unwrap();
+
+        let mode = fs::metadata(&file_path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o644);
+
+        let _ = fs::remove_dir_all(&base);
+    }
+}